@@ -0,0 +1,352 @@
+// Gitignore-aware filtering for `Treeish::walk_with_ignore`, modeled on the layered pattern
+// matcher in the `ignore` crate: each directory descended during the walk contributes a layer of
+// compiled patterns (its `.gitignore`, `.ignore`, and optionally `.git/info/exclude`), and those
+// layers are stacked root-to-leaf so that a deeper directory's patterns can override a shallower
+// one's. A candidate is excluded when its nearest matching pattern is not a negation: the deepest
+// layer with any pattern matching the candidate decides, and within that layer the last matching
+// pattern in file order wins.
+
+use std::borrow::Cow;
+use std::fs::{self, ReadDir};
+use std::path::{Path, PathBuf};
+use wax::Glob;
+
+use crate::Treeish;
+
+const IGNORE_FILE_NAMES: &[&str] = &[".gitignore", ".ignore"];
+const GIT_EXCLUDE_PATH: &str = ".git/info/exclude";
+
+/// Options controlling a gitignore-aware walk. See [`Treeish::walk_with_ignore`].
+///
+/// [`Treeish::walk_with_ignore`]: crate::Treeish::walk_with_ignore
+#[derive(Clone, Debug)]
+pub struct WalkOptions {
+    /// Skip entries whose file name begins with `.`. Defaults to `true`.
+    pub skip_hidden: bool,
+    /// Honor `.gitignore` and `.ignore` files found in each descended directory. Defaults to
+    /// `true`.
+    pub ignore_files: bool,
+    /// Honor a repository's `.git/info/exclude` file. Defaults to `true`.
+    pub git_exclude: bool,
+    /// An additional, global ignore file (e.g. a user's `core.excludesFile`) applied to every
+    /// directory in the walk. Defaults to `None`.
+    pub global_ignore_file: Option<PathBuf>,
+    /// The maximum depth to descend, where the root of the walk is depth zero. Defaults to
+    /// `None` (unbounded).
+    pub max_depth: Option<usize>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        WalkOptions {
+            skip_hidden: true,
+            ignore_files: true,
+            git_exclude: true,
+            global_ignore_file: None,
+            max_depth: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Polarity {
+    Ignore,
+    Negate,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    glob: Glob<'static>,
+    polarity: Polarity,
+}
+
+impl Pattern {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (polarity, pattern) = match line.strip_prefix('!') {
+            Some(rest) => (Polarity::Negate, rest),
+            None => (Polarity::Ignore, line),
+        };
+        // A pattern with a `/` anywhere but a single trailing position (including a leading `/`)
+        // is anchored to the directory that defines it, mirroring gitignore semantics; this must
+        // be decided before any leading `/` is stripped, or an anchored pattern like `/target`
+        // would be mistaken for an unanchored one. A pattern with no such `/` matches at any depth
+        // under that directory, so anchor it explicitly with `**/` so that `wax` matches it
+        // regardless of depth.
+        let anchored = pattern.trim_end_matches('/').contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        let pattern = if anchored {
+            Cow::Borrowed(pattern)
+        }
+        else {
+            Cow::Owned(format!("**/{}", pattern))
+        };
+        Glob::new(pattern.as_ref())
+            .ok()
+            .map(|glob| Pattern {
+                glob: glob.into_owned(),
+                polarity,
+            })
+    }
+
+    fn is_match(&self, relative: &Path) -> bool {
+        self.glob.is_match(relative)
+    }
+}
+
+#[derive(Debug)]
+struct Layer {
+    directory: PathBuf,
+    patterns: Vec<Pattern>,
+}
+
+impl Layer {
+    fn read(directory: &Path, options: &WalkOptions) -> Self {
+        let mut patterns = Vec::new();
+        if options.ignore_files {
+            for name in IGNORE_FILE_NAMES {
+                patterns.extend(read_patterns(&directory.join(name)));
+            }
+        }
+        if options.git_exclude {
+            patterns.extend(read_patterns(&directory.join(GIT_EXCLUDE_PATH)));
+        }
+        Layer {
+            directory: directory.into(),
+            patterns,
+        }
+    }
+
+    fn global(path: &Path) -> Self {
+        Layer {
+            directory: PathBuf::new(),
+            patterns: read_patterns(path),
+        }
+    }
+
+    // Returns the polarity of the last pattern in this layer that matches `candidate`, if any.
+    // `candidate` is matched relative to this layer's own directory; `None` is returned (rather
+    // than treating it as a match) if `candidate` does not descend from it. The global layer has
+    // no directory, so its patterns are tested against `candidate` as is, regardless of location.
+    fn matches(&self, candidate: &Path) -> Option<Polarity> {
+        let relative = if self.directory.as_os_str().is_empty() {
+            candidate
+        }
+        else {
+            candidate.strip_prefix(&self.directory).ok()?
+        };
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| pattern.is_match(relative))
+            .map(|pattern| pattern.polarity)
+    }
+}
+
+fn read_patterns(path: &Path) -> Vec<Pattern> {
+    fs::read_to_string(path)
+        .map(|text| text.lines().filter_map(Pattern::parse).collect())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Default)]
+struct IgnoreStack {
+    layers: Vec<Layer>,
+}
+
+impl IgnoreStack {
+    fn push(&mut self, layer: Layer) {
+        self.layers.push(layer);
+    }
+
+    fn pop(&mut self) {
+        self.layers.pop();
+    }
+
+    fn is_excluded(&self, candidate: &Path) -> bool {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|layer| layer.matches(candidate))
+            .map_or(false, |polarity| polarity == Polarity::Ignore)
+    }
+}
+
+/// An entry yielded by [`Treeish::walk_with_ignore`].
+///
+/// [`Treeish::walk_with_ignore`]: crate::Treeish::walk_with_ignore
+#[derive(Clone, Debug)]
+pub struct Entry {
+    path: PathBuf,
+}
+
+impl Entry {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Entry { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn into_path(self) -> PathBuf {
+        self.path
+    }
+}
+
+struct Frame {
+    depth: usize,
+    entries: ReadDir,
+}
+
+/// A gitignore-aware walk of a [`Treeish`]. See [`Treeish::walk_with_ignore`].
+pub struct IgnoreWalk<'a, 't> {
+    treeish: &'a Treeish<'t>,
+    options: WalkOptions,
+    ignores: IgnoreStack,
+    frames: Vec<Frame>,
+}
+
+impl<'a, 't> IgnoreWalk<'a, 't> {
+    pub(crate) fn new(treeish: &'a Treeish<'t>, options: WalkOptions) -> Self {
+        let mut ignores = IgnoreStack::default();
+        if let Some(ref global) = options.global_ignore_file {
+            ignores.push(Layer::global(global));
+        }
+        let root = treeish.root().into_owned();
+        let mut walk = IgnoreWalk {
+            treeish,
+            options,
+            ignores,
+            frames: Vec::new(),
+        };
+        walk.push_directory(&root, 0);
+        walk
+    }
+
+    fn push_directory(&mut self, directory: &Path, depth: usize) {
+        self.ignores.push(Layer::read(directory, &self.options));
+        if let Ok(entries) = fs::read_dir(directory) {
+            self.frames.push(Frame { depth, entries });
+        }
+        else {
+            // The directory could not be read (e.g. it was removed or is not a directory, as
+            // with the root of a `Treeish::Path` that names a file). Pop the layer pushed above so
+            // the stack stays aligned with the frames that are actually live.
+            self.ignores.pop();
+        }
+    }
+}
+
+impl<'a, 't> Iterator for IgnoreWalk<'a, 't> {
+    type Item = std::io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let frame = self.frames.last_mut()?;
+            match frame.entries.next() {
+                Some(Ok(entry)) => {
+                    let path = entry.path();
+                    let depth = frame.depth + 1;
+                    if self.options.skip_hidden && is_hidden(&path) {
+                        continue;
+                    }
+                    if self.ignores.is_excluded(&path) {
+                        continue;
+                    }
+                    let is_directory = entry.file_type().map_or(false, |kind| kind.is_dir());
+                    let within_depth = self
+                        .options
+                        .max_depth
+                        .map_or(true, |max_depth| depth <= max_depth);
+                    if is_directory {
+                        // Besides the gitignore-style layers above, a `GlobIn` treeish's own
+                        // exclusion globs prune the directories they match outright rather than
+                        // merely filtering their contents out afterwards, mirroring the pruning
+                        // `walk_parallel` performs; see `Treeish::is_excluded_directory`.
+                        if within_depth && !self.treeish.is_excluded_directory(&path) {
+                            self.push_directory(&path, depth);
+                        }
+                        continue;
+                    }
+                    if within_depth && self.treeish.is_semantic_match(path.as_path()) {
+                        return Some(Ok(Entry::new(path)));
+                    }
+                },
+                Some(Err(error)) => return Some(Err(error)),
+                None => {
+                    self.frames.pop();
+                    self.ignores.pop();
+                },
+            }
+        }
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map_or(false, |name| name.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_slash_anchors_pattern_to_its_own_depth() {
+        let pattern = Pattern::parse("/target").unwrap();
+        assert!(pattern.is_match(Path::new("target")));
+        assert!(!pattern.is_match(Path::new("nested/target")));
+    }
+
+    #[test]
+    fn pattern_without_slash_matches_at_any_depth() {
+        let pattern = Pattern::parse("target").unwrap();
+        assert!(pattern.is_match(Path::new("target")));
+        assert!(pattern.is_match(Path::new("nested/target")));
+    }
+
+    #[test]
+    fn interior_slash_anchors_pattern_to_its_own_depth() {
+        let pattern = Pattern::parse("a/b").unwrap();
+        assert!(pattern.is_match(Path::new("a/b")));
+        assert!(!pattern.is_match(Path::new("nested/a/b")));
+    }
+
+    #[test]
+    fn leading_bang_negates_pattern() {
+        let pattern = Pattern::parse("!keep.txt").unwrap();
+        assert_eq!(pattern.polarity, Polarity::Negate);
+    }
+
+    #[test]
+    fn exclusion_prunes_directory_during_ignore_walk() {
+        let root = std::env::temp_dir().join(format!(
+            "treeish-ignore-walk-test-{}-{}",
+            std::process::id(),
+            line!(),
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("keep")).unwrap();
+        fs::create_dir_all(root.join("skip")).unwrap();
+        fs::write(root.join("keep/a.txt"), b"").unwrap();
+        fs::write(root.join("skip/b.txt"), b"").unwrap();
+
+        let expression = format!("{}::**/*.txt::!skip/**", root.display());
+        let treeish = crate::Treeish::new(&expression).unwrap();
+        let paths: Vec<_> = treeish
+            .walk_with_ignore(WalkOptions::default())
+            .filter_map(Result::ok)
+            .map(Entry::into_path)
+            .collect();
+
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(paths.iter().any(|path| path.ends_with("keep/a.txt")));
+        assert!(!paths.iter().any(|path| path.ends_with("skip/b.txt")));
+    }
+}
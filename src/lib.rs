@@ -1,23 +1,30 @@
+mod ignore;
+mod parallel;
 mod parse;
 
 #[cfg(feature = "miette")]
-use miette::Diagnostic;
+use miette::{Diagnostic, LabeledSpan, SourceCode};
 use std::borrow::Cow;
+use std::fmt;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
-use wax::{CandidatePath, Glob, Walk, WalkBehavior};
+use wax::{CandidatePath, Glob, MatchedText, Walk, WalkBehavior};
 
-use crate::parse::{ParseError, Partitioned};
+pub use crate::ignore::{Entry, IgnoreWalk, WalkOptions};
+pub use crate::parallel::WalkState;
+use crate::parse::{ParseError, Partitioned, SEPARATOR};
 
-// A treeish uses the following syntax:
+// A treeish uses the following syntax, with `::` separating the tree path from the glob
+// pattern(s) that follow it:
 //
 // `C:\Users::**/*.txt`
 // `\\.\COM1::**/*.txt`
 // `\\?\UNC\server\share::**/*.txt`
 // `/mnt/media1::**/*.txt`
 //
-// This uses `::` as the separator. Consider `>>`.
+// A literal `::` within the tree path can be escaped as `\::`. The separator need not be `::`
+// at all: see `Treeish::parse_with`.
 
 trait Empty {
     fn is_empty(&self) -> bool;
@@ -54,6 +61,33 @@ impl Empty for PathBuf {
     }
 }
 
+// Candidate paths are normalized to use `/` as a separator (see `wax::CandidatePath`), so prefixes
+// are stripped component-wise against that separator rather than the platform's native one.
+fn is_component_prefix(prefix: &str, candidate: &str) -> bool {
+    strip_component_prefix(prefix, candidate).is_some()
+}
+
+// Returns the remainder of `candidate` with the leading path components in `prefix` removed, or
+// `None` if `candidate` is not `prefix` itself or a descendant of it. This is a component-wise
+// comparison: a prefix of `/a/b` does not match a candidate of `/a/bc`.
+fn strip_component_prefix<'c>(prefix: &str, candidate: &'c str) -> Option<&'c str> {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        return Some(candidate.trim_start_matches('/'));
+    }
+    match candidate.strip_prefix(prefix)? {
+        "" => Some(""),
+        rest => rest.strip_prefix('/'),
+    }
+}
+
+// The inverse of `parse::unescape`: replaces any literal occurrence of `separator` in `text` with
+// `\<separator>`, so that the result, embedded in a larger treeish expression, parses back to
+// `text` rather than being split on.
+fn escape(text: &str, separator: &str) -> String {
+    text.replace(separator, &format!("\\{}", separator))
+}
+
 #[derive(Debug, Error)]
 #[error(transparent)]
 #[cfg_attr(feature = "miette", derive(Diagnostic))]
@@ -78,12 +112,23 @@ impl From<RuleError> for BuildError {
     }
 }
 
+impl BuildError {
+    // Used by the parser, which knows where the glob fragment of a treeish expression begins and
+    // so can place `error`'s span(s) relative to the full expression rather than the bare glob
+    // fragment `wax` actually saw.
+    pub(crate) fn glob_at(expression: &str, offset: usize, error: wax::BuildError) -> Self {
+        BuildError {
+            kind: BuildErrorKind::Glob(GlobError::at(expression, offset, error)),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 #[non_exhaustive]
 #[cfg_attr(feature = "miette", derive(Diagnostic))]
 enum BuildErrorKind {
     #[error(transparent)]
-    Glob(wax::BuildError),
+    Glob(GlobError),
     #[error(transparent)]
     Parse(ParseError<'static>),
     #[error(transparent)]
@@ -92,7 +137,67 @@ enum BuildErrorKind {
 
 impl From<wax::BuildError> for BuildErrorKind {
     fn from(error: wax::BuildError) -> Self {
-        BuildErrorKind::Glob(error)
+        BuildErrorKind::Glob(error.into())
+    }
+}
+
+// Wraps a `wax::BuildError` that occurred while compiling the glob fragment of a treeish
+// expression. When the fragment is a strict suffix of a larger expression (as with
+// `tree::glob`), `offset` repositions `error`'s span(s) so that they point into the full
+// expression instead of the bare fragment `wax` saw.
+#[derive(Debug, Error)]
+#[error("{error}")]
+pub struct GlobError {
+    expression: Option<String>,
+    offset: usize,
+    #[source]
+    error: wax::BuildError,
+}
+
+impl GlobError {
+    fn at(expression: &str, offset: usize, error: wax::BuildError) -> Self {
+        GlobError {
+            expression: Some(expression.into()),
+            offset,
+            error,
+        }
+    }
+}
+
+impl From<wax::BuildError> for GlobError {
+    fn from(error: wax::BuildError) -> Self {
+        GlobError {
+            expression: None,
+            offset: 0,
+            error,
+        }
+    }
+}
+
+#[cfg(feature = "miette")]
+impl Diagnostic for GlobError {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        self.error.code()
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        match self.expression {
+            Some(ref expression) => Some(expression),
+            None => self.error.source_code(),
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let offset = self.offset;
+        self.error.labels().map(|labels| {
+            Box::new(labels.map(move |label| {
+                LabeledSpan::new(
+                    label.label().map(String::from),
+                    label.offset() + offset,
+                    label.len(),
+                )
+            })) as Box<dyn Iterator<Item = _>>
+        })
     }
 }
 
@@ -113,6 +218,14 @@ impl From<RuleError> for BuildErrorKind {
 enum RuleError {
     #[error("")]
     RootedPatternIn,
+    // A `::`-separated segment following the inclusion glob did not begin with `!` and so cannot
+    // be classified as an exclusion.
+    #[error("")]
+    MalformedExclusion,
+    // Exclusions were present without a tree path to join them (and the inclusion glob) to, e.g.
+    // `::**/*.rs::!**/target/**`.
+    #[error("")]
+    ExclusionWithoutTree,
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -218,7 +331,8 @@ pub enum Treeish<'t> {
     Glob(TreeishGlob<'t>),
     GlobIn {
         tree: TreeishPath<'t>,
-        glob: Unrooted<TreeishGlob<'t>>,
+        include: Unrooted<TreeishGlob<'t>>,
+        excludes: Vec<Unrooted<TreeishGlob<'t>>>,
     },
 }
 
@@ -232,25 +346,46 @@ impl<'t> Treeish<'t> {
         parse::parse(expression)?.try_into()
     }
 
+    // Like `new`, but splits the tree path from the rest of `expression` on `separator` rather
+    // than `::`. A `\` immediately before `separator` escapes it, so it is read as ordinary text
+    // within the tree path rather than a split point.
+    pub fn parse_with(expression: &'t str, separator: &str) -> Result<Self, BuildError> {
+        parse::parse_with(expression, separator)?.try_into()
+    }
+
     pub fn into_owned(self) -> Treeish<'static> {
         use Treeish::{Glob, GlobIn, Path};
 
         match self {
             Path(path) => Path(path.into_owned()),
             Glob(glob) => Glob(glob.into_owned()),
-            GlobIn { tree, glob } => GlobIn {
+            GlobIn {
+                tree,
+                include,
+                excludes,
+            } => GlobIn {
                 tree: tree.into_owned(),
-                glob: glob.into_owned(),
+                include: include.into_owned(),
+                excludes: excludes.into_iter().map(Unrooted::into_owned).collect(),
             },
         }
     }
 
-    pub fn walk(&self) -> Walk {
+    pub fn walk(&self) -> impl Iterator<Item = <Walk as Iterator>::Item> + 't {
         self.walk_with_behavior(WalkBehavior::default())
     }
 
-    pub fn walk_with_behavior(&self, behavior: impl Into<WalkBehavior>) -> Walk {
-        match self {
+    // `wax::Walk` drives its own directory recursion internally and exposes no way to tell it to
+    // skip a subtree once descended, so this is a real limitation rather than a deferral: an
+    // exclusion glob can only filter entries out here, after the fact, and every excluded
+    // directory is still fully walked. `walk_with_ignore` and `walk_parallel` drive their own
+    // recursion and so can prune excluded directories outright; prefer one of those over this
+    // method when walking large trees with exclusions.
+    pub fn walk_with_behavior(
+        &self,
+        behavior: impl Into<WalkBehavior>,
+    ) -> impl Iterator<Item = <Walk as Iterator>::Item> + 't {
+        let walk = match self {
             Treeish::Path(ref path) => {
                 let glob = Glob::new("").unwrap();
                 glob.walk_with_behavior(path.as_ref(), behavior)
@@ -258,15 +393,177 @@ impl<'t> Treeish<'t> {
             },
             // TODO: `.` isn't truly cross-platform.
             Treeish::Glob(ref glob) => glob.walk_with_behavior(".", behavior),
-            Treeish::GlobIn { ref tree, ref glob } => {
-                glob.walk_with_behavior(tree.as_ref(), behavior)
+            Treeish::GlobIn {
+                ref tree,
+                ref include,
+                ..
+            } => include.walk_with_behavior(tree.as_ref(), behavior),
+        };
+        // Excludes are parsed as patterns relative to `tree` (the same as `include`), not as
+        // patterns against the full walked path, so the `tree` prefix has to be stripped from each
+        // entry before testing it against them; see `Treeish::is_semantic_match`, which does the
+        // same.
+        let tree_and_excludes: Option<(TreeishPath<'t>, Vec<Glob<'t>>)> = match self {
+            Treeish::GlobIn {
+                ref tree,
+                ref excludes,
+                ..
+            } if !excludes.is_empty() => Some((
+                tree.clone(),
+                excludes
+                    .iter()
+                    .map(|exclude| exclude.as_ref().as_ref().clone())
+                    .collect(),
+            )),
+            _ => None,
+        };
+        walk.filter(move |entry| match entry {
+            Ok(entry) => match tree_and_excludes {
+                Some((ref tree, ref excludes)) => {
+                    let candidate = CandidatePath::from(entry.path());
+                    let tree = CandidatePath::from(tree.as_ref());
+                    match strip_component_prefix(tree.as_ref(), candidate.as_ref()) {
+                        Some(relative) => !excludes
+                            .iter()
+                            .any(|exclude| exclude.is_match(CandidatePath::from(relative))),
+                        None => true,
+                    }
+                },
+                None => true,
+            },
+            Err(_) => true,
+        })
+    }
+
+    /// Walks this treeish like [`Treeish::walk`], pairing each entry with the [`MatchedText`] of
+    /// its glob captures, resolved against the entry's path relative to the walk root (for
+    /// `GlobIn`, that means relative to `tree`, so that capture groups line up with `include`
+    /// rather than with the full path). `Treeish::Path` has no glob to capture against, so it
+    /// pairs every entry with `None`; a failed directory entry also pairs with `None`.
+    ///
+    /// [`Treeish::walk`]: crate::Treeish::walk
+    /// [`MatchedText`]: wax::MatchedText
+    pub fn walk_captures(
+        &self,
+    ) -> impl Iterator<Item = (<Walk as Iterator>::Item, Option<MatchedText<'static>>)> + 't {
+        enum Matcher<'t> {
+            Path,
+            Glob(Glob<'t>),
+            GlobIn(Cow<'t, Path>, Glob<'t>),
+        }
+
+        let matcher = match self {
+            Treeish::Path(_) => Matcher::Path,
+            Treeish::Glob(ref glob) => Matcher::Glob(glob.as_ref().clone()),
+            Treeish::GlobIn {
+                ref tree,
+                ref include,
+                ..
+            } => Matcher::GlobIn(tree.clone().into(), include.as_ref().as_ref().clone()),
+        };
+        self.walk().map(move |entry| {
+            let captures = match (&matcher, entry.as_ref()) {
+                (Matcher::Path, _) | (_, Err(_)) => None,
+                (Matcher::Glob(glob), Ok(entry)) => {
+                    glob.captures(CandidatePath::from(entry.path()))
+                },
+                (Matcher::GlobIn(tree, include), Ok(entry)) => {
+                    let candidate = CandidatePath::from(entry.path());
+                    let tree = CandidatePath::from(tree.as_ref());
+                    strip_component_prefix(tree.as_ref(), candidate.as_ref())
+                        .and_then(|relative| include.captures(CandidatePath::from(relative)))
+                },
+            };
+            (entry, captures.map(MatchedText::into_owned))
+        })
+    }
+
+    // The directory at which a walk of this treeish begins: the tree path itself for `Path` and
+    // `GlobIn`, or the current directory for a bare `Glob`.
+    pub(crate) fn root(&self) -> Cow<'_, Path> {
+        match self {
+            Treeish::Path(ref path) => path.as_ref().into(),
+            // TODO: `.` isn't truly cross-platform.
+            Treeish::Glob(_) => Path::new(".").into(),
+            Treeish::GlobIn { ref tree, .. } => tree.as_ref().into(),
+        }
+    }
+
+    pub fn walk_with_ignore(&self, options: WalkOptions) -> IgnoreWalk<'_, 't> {
+        IgnoreWalk::new(self, options)
+    }
+
+    /// Walks this treeish using a pool of `threads` worker threads (or the number of available
+    /// cores if `threads` is zero), invoking a fresh instance of `visitor` on each thread for
+    /// every directory descended and every matched entry. Returning [`WalkState::Skip`] from a
+    /// directory entry prunes its subtree without descending into it; returning
+    /// [`WalkState::Quit`] stops the walk on every thread.
+    ///
+    /// Directories matched by this treeish's own exclusion globs, if any, are pruned
+    /// automatically and are never passed to `visitor` (see [`Treeish::walk_with_behavior`]).
+    ///
+    /// This unlocks traversal throughput that the single-threaded [`Treeish::walk`] cannot
+    /// reach on large trees.
+    ///
+    /// [`Treeish::walk_with_behavior`]: crate::Treeish::walk_with_behavior
+    /// [`Treeish::walk`]: crate::Treeish::walk
+    pub fn walk_parallel<F, V>(&self, threads: usize, visitor: F)
+    where
+        F: Fn() -> V,
+        V: FnMut(std::io::Result<Entry>) -> WalkState + Send,
+        Treeish<'t>: Sync,
+    {
+        parallel::walk(self, threads, visitor)
+    }
+
+    // Whether `path`, naming a directory encountered during a walk, is matched by one of this
+    // treeish's exclusion globs and so should be pruned without descending into it. Only
+    // `GlobIn` treeishes carry exclusions; all other variants are never pruned this way.
+    pub(crate) fn is_excluded_directory(&self, path: &Path) -> bool {
+        match self {
+            Treeish::GlobIn {
+                ref tree,
+                ref excludes,
+                ..
+            } if !excludes.is_empty() => {
+                let candidate = CandidatePath::from(path);
+                let tree = CandidatePath::from(tree.as_ref());
+                match strip_component_prefix(tree.as_ref(), candidate.as_ref()) {
+                    Some(relative) => excludes.iter().any(|exclude| {
+                        exclude.as_ref().is_match(CandidatePath::from(relative))
+                    }),
+                    None => false,
+                }
             },
+            _ => false,
         }
     }
 
     pub fn is_semantic_match<'p>(&self, path: impl Into<CandidatePath<'p>>) -> bool {
-        let _path = path.into();
-        todo!()
+        let candidate = path.into();
+        match self {
+            Treeish::Path(ref tree) => {
+                let tree = CandidatePath::from(tree.as_ref());
+                is_component_prefix(tree.as_ref(), candidate.as_ref())
+            },
+            Treeish::Glob(ref glob) => glob.as_ref().is_match(candidate),
+            Treeish::GlobIn {
+                ref tree,
+                ref include,
+                ref excludes,
+            } => {
+                let tree = CandidatePath::from(tree.as_ref());
+                match strip_component_prefix(tree.as_ref(), candidate.as_ref()) {
+                    Some(relative) => {
+                        include.as_ref().is_match(CandidatePath::from(relative))
+                            && !excludes.iter().any(|exclude| {
+                                exclude.as_ref().is_match(CandidatePath::from(relative))
+                            })
+                    },
+                    None => false,
+                }
+            },
+        }
     }
 
     pub fn path(self) -> Option<Cow<'t, Path>> {
@@ -283,12 +580,20 @@ impl<'t> Treeish<'t> {
         }
     }
 
-    pub fn glob_in(self) -> Option<(Cow<'t, Path>, Glob<'t>)> {
+    pub fn glob_in(self) -> Option<(Cow<'t, Path>, Glob<'t>, Vec<Glob<'t>>)> {
         match self {
             Treeish::GlobIn {
                 tree: TreeishPath(tree),
-                glob: Unrooted(TreeishGlob(glob)),
-            } => Some((tree, glob)),
+                include: Unrooted(TreeishGlob(include)),
+                excludes,
+            } => Some((
+                tree,
+                include,
+                excludes
+                    .into_iter()
+                    .map(|Unrooted(TreeishGlob(exclude))| exclude)
+                    .collect(),
+            )),
             _ => None,
         }
     }
@@ -302,6 +607,45 @@ impl<'t> Treeish<'t> {
     }
 }
 
+// Emits this treeish using the default `::` separator, escaping any literal occurrence of it so
+// that the result re-parses (via `Treeish::new`) to an equivalent value. Note that this always
+// uses `::`, regardless of any custom separator a `Treeish` built via `Treeish::parse_with` was
+// originally expressed with.
+impl<'t> fmt::Display for Treeish<'t> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Treeish::Path(ref path) => {
+                write!(f, "{}", escape(&path.as_ref().to_string_lossy(), SEPARATOR))
+            },
+            Treeish::Glob(ref glob) => {
+                write!(f, "{}", escape(&glob.as_ref().to_string(), SEPARATOR))
+            },
+            Treeish::GlobIn {
+                ref tree,
+                ref include,
+                ref excludes,
+            } => {
+                write!(
+                    f,
+                    "{}{}{}",
+                    escape(&tree.as_ref().to_string_lossy(), SEPARATOR),
+                    SEPARATOR,
+                    escape(&include.as_ref().as_ref().to_string(), SEPARATOR),
+                )?;
+                for exclude in excludes {
+                    write!(
+                        f,
+                        "{}!{}",
+                        SEPARATOR,
+                        escape(&exclude.as_ref().as_ref().to_string(), SEPARATOR),
+                    )?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
 impl<'t> From<&'t Path> for Treeish<'t> {
     fn from(path: &'t Path) -> Self {
         // TODO: May be empty! Gross.
@@ -319,7 +663,7 @@ impl<'t> TryFrom<Glob<'t>> for Treeish<'t> {
         Treeish::try_from(match (path.non_empty(), glob.non_empty()) {
             (Some(path), None) => Some(Partitioned::Path(path.into())),
             (None, Some(glob)) => Some(Partitioned::Glob(glob)),
-            (Some(path), Some(glob)) => Some(Partitioned::GlobIn(path.into(), glob)),
+            (Some(path), Some(glob)) => Some(Partitioned::GlobIn(path.into(), glob, Vec::new())),
             (None, None) => None,
         })
     }
@@ -334,17 +678,21 @@ impl<'t> TryFrom<Option<Partitioned<'t>>> for Treeish<'t> {
             match partitioned {
                 Partitioned::Path(path) => Ok(Treeish::Path(TreeishPath(path))),
                 Partitioned::Glob(glob) => Ok(Treeish::Glob(TreeishGlob(glob))),
-                Partitioned::GlobIn(path, glob) => {
-                    if glob.has_root() {
+                Partitioned::GlobIn(path, include, excludes) => {
+                    if include.has_root() || excludes.iter().any(Glob::has_root) {
                         // TODO: Provide details in the error.
-                        // If the glob still has a root, then it cannot be joined to a native path
+                        // If a glob still has a root, then it cannot be joined to a native path
                         // non-destructively. Such treeish expressions are not allowed.
                         Err(RuleError::RootedPatternIn.into())
                     }
                     else {
                         Ok(Treeish::GlobIn {
                             tree: TreeishPath(path),
-                            glob: Unrooted(TreeishGlob(glob)),
+                            include: Unrooted(TreeishGlob(include)),
+                            excludes: excludes
+                                .into_iter()
+                                .map(|exclude| Unrooted(TreeishGlob(exclude)))
+                                .collect(),
                         })
                     }
                 },
@@ -358,4 +706,107 @@ impl<'t> TryFrom<Option<Partitioned<'t>>> for Treeish<'t> {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_treeish_matches_only_its_own_prefix() {
+        let treeish = Treeish::new("src").unwrap();
+        assert!(treeish.is_semantic_match(Path::new("src/lib.rs")));
+        assert!(!treeish.is_semantic_match(Path::new("tests/lib.rs")));
+    }
+
+    #[test]
+    fn glob_treeish_matches_anywhere() {
+        let treeish = Treeish::new("**/*.rs").unwrap();
+        assert!(treeish.is_semantic_match(Path::new("src/lib.rs")));
+        assert!(treeish.is_semantic_match(Path::new("lib.rs")));
+        assert!(!treeish.is_semantic_match(Path::new("src/lib.txt")));
+    }
+
+    #[test]
+    fn glob_in_treeish_matches_only_within_its_tree_and_honors_excludes() {
+        let treeish = Treeish::new("src::**/*.rs::!**/generated/**").unwrap();
+        assert!(treeish.is_semantic_match(Path::new("src/lib.rs")));
+        assert!(!treeish.is_semantic_match(Path::new("tests/lib.rs")));
+        assert!(!treeish.is_semantic_match(Path::new("src/generated/codegen.rs")));
+    }
+
+    // Excludes are relative to the tree, so `walk`'s exclusion filter strips the tree prefix from
+    // each walked path before testing it; without that, a tree-relative exclude that isn't itself
+    // prefixed with `**/` (the common case, and the form used elsewhere in this crate's own tests)
+    // would never match anything.
+    #[test]
+    fn walk_excludes_tree_relative_directory_without_double_star_prefix() {
+        let root = std::env::temp_dir().join(format!(
+            "treeish-walk-exclude-test-{}-{}",
+            std::process::id(),
+            line!(),
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("keep")).unwrap();
+        std::fs::create_dir_all(root.join("skip")).unwrap();
+        std::fs::write(root.join("keep/a.txt"), b"").unwrap();
+        std::fs::write(root.join("skip/b.txt"), b"").unwrap();
+
+        let expression = format!("{}::**/*.txt::!skip/**", root.display());
+        let treeish = Treeish::new(&expression).unwrap();
+        let paths: Vec<_> = treeish
+            .walk()
+            .filter_map(Result::ok)
+            .map(|entry| entry.path().to_path_buf())
+            .collect();
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(paths.iter().any(|path| path.ends_with("keep/a.txt")));
+        assert!(!paths.iter().any(|path| path.ends_with("skip/b.txt")));
+    }
+
+    #[test]
+    fn walk_captures_pairs_path_entries_with_no_captures() {
+        let root = std::env::temp_dir().join(format!(
+            "treeish-walk-captures-path-test-{}-{}",
+            std::process::id(),
+            line!(),
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.rs"), b"").unwrap();
+
+        let treeish = Treeish::new(root.to_str().unwrap()).unwrap();
+        let entries: Vec<_> = treeish
+            .walk_captures()
+            .filter_map(|(entry, captures)| entry.ok().map(|entry| (entry, captures)))
+            .collect();
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(!entries.is_empty());
+        assert!(entries.iter().all(|(_, captures)| captures.is_none()));
+    }
+
+    #[test]
+    fn walk_captures_pairs_glob_in_entries_with_captures() {
+        let root = std::env::temp_dir().join(format!(
+            "treeish-walk-captures-glob-test-{}-{}",
+            std::process::id(),
+            line!(),
+        ));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.rs"), b"").unwrap();
+
+        let expression = format!("{}::*.rs", root.display());
+        let treeish = Treeish::new(&expression).unwrap();
+        let entries: Vec<_> = treeish
+            .walk_captures()
+            .filter_map(|(entry, captures)| entry.ok().map(|entry| (entry, captures)))
+            .collect();
+
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(!entries.is_empty());
+        assert!(entries.iter().all(|(_, captures)| captures.is_some()));
+    }
+}
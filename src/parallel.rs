@@ -0,0 +1,206 @@
+// A work-stealing-style directory walker for `Treeish::walk_parallel`, modeled on the parallel
+// walker in the `ignore` crate. There is no per-thread deque here, just a single queue of pending
+// directories guarded by a mutex: idle workers block on a condition variable until another worker
+// pushes more work (having descended a directory) or every worker agrees there is none left.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+use crate::{Entry, Treeish};
+
+/// Controls how [`Treeish::walk_parallel`] proceeds after a visitor inspects an entry.
+///
+/// [`Treeish::walk_parallel`]: crate::Treeish::walk_parallel
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WalkState {
+    /// Continue the walk.
+    Continue,
+    /// Don't descend into the directory this entry names. Has no effect on non-directory
+    /// entries.
+    Skip,
+    /// Stop the walk entirely, on every worker thread.
+    Quit,
+}
+
+#[derive(Default)]
+struct State {
+    queue: Vec<PathBuf>,
+    // The number of directories that have been pushed but not yet finished, whether they are
+    // still sitting in `queue` or are currently being read by a worker. The walk is done once
+    // this reaches zero with `queue` empty.
+    pending: usize,
+}
+
+fn pop(state: &Mutex<State>, ready: &Condvar, quit: &AtomicBool) -> Option<PathBuf> {
+    let mut state = state.lock().unwrap();
+    loop {
+        if quit.load(Ordering::Relaxed) {
+            return None;
+        }
+        if let Some(directory) = state.queue.pop() {
+            return Some(directory);
+        }
+        if state.pending == 0 {
+            return None;
+        }
+        state = ready.wait(state).unwrap();
+    }
+}
+
+fn push(state: &Mutex<State>, ready: &Condvar, directory: PathBuf) {
+    let mut state = state.lock().unwrap();
+    state.queue.push(directory);
+    state.pending += 1;
+    drop(state);
+    ready.notify_all();
+}
+
+fn finish(state: &Mutex<State>, ready: &Condvar) {
+    let mut state = state.lock().unwrap();
+    state.pending -= 1;
+    drop(state);
+    ready.notify_all();
+}
+
+fn stop(quit: &AtomicBool, ready: &Condvar) {
+    quit.store(true, Ordering::Relaxed);
+    ready.notify_all();
+}
+
+pub(crate) fn walk<'t, F, V>(treeish: &Treeish<'t>, threads: usize, visitor: F)
+where
+    F: Fn() -> V,
+    V: FnMut(io::Result<Entry>) -> WalkState + Send,
+    Treeish<'t>: Sync,
+{
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let state = Mutex::new(State {
+        queue: vec![treeish.root().into_owned()],
+        pending: 1,
+    });
+    let ready = Condvar::new();
+    let quit = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let mut visit = visitor();
+            let state = &state;
+            let ready = &ready;
+            let quit = &quit;
+            scope.spawn(move || {
+                while let Some(directory) = pop(state, ready, quit) {
+                    drain(treeish, &directory, state, ready, quit, &mut visit);
+                }
+            });
+        }
+    });
+}
+
+// Reads one directory's entries, passing each subdirectory not pruned by this treeish's exclusions
+// and each file that matches this treeish to `visit`, then marks this unit of work finished so that
+// sibling workers can observe the queue draining.
+fn drain<'t, V>(
+    treeish: &Treeish<'t>,
+    directory: &PathBuf,
+    state: &Mutex<State>,
+    ready: &Condvar,
+    quit: &AtomicBool,
+    visit: &mut V,
+) where
+    V: FnMut(io::Result<Entry>) -> WalkState,
+{
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(error) => {
+            if visit(Err(error)) == WalkState::Quit {
+                stop(quit, ready);
+            }
+            finish(state, ready);
+            return;
+        },
+    };
+    for entry in entries {
+        if quit.load(Ordering::Relaxed) {
+            break;
+        }
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                if visit(Err(error)) == WalkState::Quit {
+                    stop(quit, ready);
+                    break;
+                }
+                continue;
+            },
+        };
+        let path = entry.path();
+        let is_directory = entry.file_type().map_or(false, |kind| kind.is_dir());
+        if is_directory {
+            if treeish.is_excluded_directory(&path) {
+                continue;
+            }
+            match visit(Ok(Entry::new(path.clone()))) {
+                WalkState::Continue => push(state, ready, path),
+                WalkState::Skip => {},
+                WalkState::Quit => {
+                    stop(quit, ready);
+                    break;
+                },
+            }
+        }
+        else if treeish.is_semantic_match(path.as_path()) && visit(Ok(Entry::new(path))) == WalkState::Quit
+        {
+            stop(quit, ready);
+            break;
+        }
+    }
+    finish(state, ready);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn walk_parallel_prunes_excluded_directories_and_visits_matches() {
+        let root = std::env::temp_dir().join(format!(
+            "treeish-walk-parallel-test-{}-{}",
+            std::process::id(),
+            line!(),
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("keep")).unwrap();
+        fs::create_dir_all(root.join("skip")).unwrap();
+        fs::write(root.join("keep/a.txt"), b"").unwrap();
+        fs::write(root.join("skip/b.txt"), b"").unwrap();
+
+        let expression = format!("{}::**/*.txt::!skip/**", root.display());
+        let treeish = Treeish::new(&expression).unwrap();
+        let paths = Mutex::new(Vec::new());
+
+        walk(&treeish, 2, || {
+            |entry: std::io::Result<Entry>| {
+                if let Ok(entry) = entry {
+                    paths.lock().unwrap().push(entry.into_path());
+                }
+                WalkState::Continue
+            }
+        });
+
+        fs::remove_dir_all(&root).unwrap();
+
+        let paths = paths.into_inner().unwrap();
+        assert!(paths.iter().any(|path| path.ends_with("keep/a.txt")));
+        assert!(!paths.iter().any(|path| path.ends_with("skip/b.txt")));
+    }
+}
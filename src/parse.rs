@@ -1,36 +1,135 @@
 #[cfg(feature = "miette")]
-use miette::Diagnostic;
+use miette::{Diagnostic, LabeledSpan, SourceCode};
 use nom::error::VerboseError as NomError;
 use std::borrow::Cow;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 use wax::{self, Glob};
 
-use crate::{BuildError, Empty};
+use crate::{BuildError, Empty, RuleError};
 
 type Input<'i> = &'i str;
 type ErrorStack<'i> = NomError<Input<'i>>;
 type ErrorMode<'i> = nom::Err<ErrorStack<'i>>;
 
+pub(crate) const SEPARATOR: &str = "::";
+
 #[derive(Debug, Error)]
-#[error("")]
-#[cfg_attr(feature = "miette", derive(Diagnostic))]
+#[error("failed to parse treeish expression")]
 pub struct ParseError<'t> {
     expression: Cow<'t, str>,
+    // The byte ranges of the tree path and offending glob fragment either side of the `::`
+    // separator, if known. Used to label the expression with `miette` rather than reporting an
+    // opaque failure.
+    tree: Option<(usize, usize)>,
+    glob: Option<(usize, usize)>,
+    #[source]
+    cause: Option<ParseErrorCause>,
 }
 
 impl<'t> ParseError<'t> {
-    // TODO: Provide details about parsing in the error.
-    fn new(expression: &'t str) -> Self {
+    // Used when `nom` cannot even determine where a `::` split would begin; recovers whatever
+    // tree/glob spans it can from the raw expression, with no specific cause to report.
+    fn new(expression: &'t str, separator: &str) -> Self {
+        let (tree, glob) = match expression.find(separator) {
+            Some(index) => (
+                Some((0, index)),
+                Some((index + separator.len(), expression.len())),
+            ),
+            None => (None, None),
+        };
         ParseError {
             expression: expression.into(),
+            tree,
+            glob,
+            cause: None,
+        }
+    }
+
+    // Used once the tree path has already been split from the rest of the expression: `tree` is
+    // its span (`None` if it was empty), `fragment` is the span of the specific glob (inclusion or
+    // exclusion) that failed to parse, and `cause` is why.
+    fn for_fragment(
+        expression: &'t str,
+        tree: Option<(usize, usize)>,
+        fragment: (usize, usize),
+        cause: ParseErrorCause,
+    ) -> Self {
+        ParseError {
+            expression: expression.into(),
+            tree,
+            glob: Some(fragment),
+            cause: Some(cause),
         }
     }
 
     pub fn into_owned(self) -> ParseError<'static> {
-        let ParseError { expression } = self;
+        let ParseError {
+            expression,
+            tree,
+            glob,
+            cause,
+        } = self;
         ParseError {
             expression: expression.into_owned().into(),
+            tree,
+            glob,
+            cause,
+        }
+    }
+}
+
+// Why a treeish expression's tree/glob split failed to produce a usable glob fragment.
+#[derive(Debug, Error)]
+enum ParseErrorCause {
+    #[error(transparent)]
+    Glob(#[from] wax::BuildError),
+    #[error(transparent)]
+    Rule(#[from] RuleError),
+}
+
+#[cfg(feature = "miette")]
+impl<'t> Diagnostic for ParseError<'t> {
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(self.expression.as_ref())
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let tree = self.tree.map(|(start, end)| {
+            LabeledSpan::new(Some("in this tree path".into()), start, end - start)
+        });
+        let glob = self.glob.map(|(start, end)| {
+            LabeledSpan::new(Some("in this glob pattern".into()), start, end - start)
+        });
+        // If `cause` is itself a `wax::BuildError`, it may carry its own internal label(s)
+        // pinpointing the malformed token; reposition those using the start of the offending
+        // fragment so they land in the full expression rather than the bare fragment `wax` saw.
+        let nested = match (&self.cause, self.glob) {
+            (Some(ParseErrorCause::Glob(error)), Some((offset, _))) => {
+                error.labels().map(|labels| {
+                    labels
+                        .map(move |label| {
+                            LabeledSpan::new(
+                                label.label().map(String::from),
+                                label.offset() + offset,
+                                label.len(),
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+            },
+            _ => None,
+        };
+        let labels: Vec<_> = tree
+            .into_iter()
+            .chain(glob)
+            .chain(nested.into_iter().flatten())
+            .collect();
+        if labels.is_empty() {
+            None
+        }
+        else {
+            Some(Box::new(labels.into_iter()))
         }
     }
 }
@@ -39,58 +138,264 @@ impl<'t> ParseError<'t> {
 pub enum Partitioned<'t> {
     Path(Cow<'t, Path>),
     Glob(Glob<'t>),
-    GlobIn(Cow<'t, Path>, Glob<'t>),
+    GlobIn(Cow<'t, Path>, Glob<'t>, Vec<Glob<'t>>),
 }
 
-// TODO: Implement escaping of the `::` separator.
 pub fn parse(expression: &str) -> Result<Option<Partitioned>, BuildError> {
-    use nom::bytes::complete as bytes;
-    use nom::{branch, combinator, sequence};
+    parse_with(expression, SEPARATOR)
+}
+
+// Like `parse`, but splits the tree path from the rest of the expression on `separator` rather
+// than the default `::`. A `\` immediately before `separator` escapes it, so it is read as
+// ordinary text within the tree path rather than a split point; see `split_at_separator`.
+pub fn parse_with<'i>(expression: &'i str, separator: &str) -> Result<Option<Partitioned<'i>>, BuildError> {
+    use nom::{branch, combinator};
 
     combinator::all_consuming(branch::alt((
-        combinator::map(
-            sequence::separated_pair(
-                bytes::take_until::<_, _, ErrorStack<'_>>("::"),
-                bytes::tag("::"),
-                combinator::rest,
-            ),
-            |(path, glob)| {
-                Glob::new(glob).map(|glob| {
-                    Some(match Path::new(path).non_empty() {
-                        Some(path) => Partitioned::GlobIn(path.into(), glob),
-                        _ => Partitioned::Glob(glob),
-                    })
-                })
-            },
-        ),
-        combinator::map(combinator::rest, |expression| {
-            Glob::new(expression)
+        combinator::map(separator_split(separator), |(tree, rest, offset)| {
+            parse_tree(expression, separator, tree, rest, offset)
+        }),
+        combinator::map(combinator::rest, |remainder: &str| {
+            Glob::new(remainder)
                 .map(|glob| {
                     glob.non_empty().map(|glob| {
-                        // There is no `::` separator. Attempt to parse a glob expression, but prefer
+                        // There is no separator. Attempt to parse a glob expression, but prefer
                         // emitting native paths if at all possible.
                         let (path, glob) = glob.partition();
                         match (path.non_empty(), glob.non_empty()) {
-                            (Some(path), Some(glob)) => Partitioned::GlobIn(path.into(), glob),
+                            (Some(path), Some(glob)) => {
+                                Partitioned::GlobIn(path.into(), glob, Vec::new())
+                            },
                             (None, Some(glob)) => Partitioned::Glob(glob),
                             (Some(path), None) => Partitioned::Path(path.into()),
                             (None, None) => unreachable!(),
                         }
                     })
                 })
+                .map_err(|error| BuildError::glob_at(remainder, 0, error))
                 .or_else(|_| {
-                    Ok(Path::new(expression)
+                    Ok(Path::new(remainder)
                         .non_empty()
                         .map(|path| Partitioned::Path(path.into())))
                 })
         }),
     )))(expression)
-    .map(|(_, treeish)| treeish.map_err(From::from))
-    .unwrap_or_else(|_: ErrorMode| {
-        // TODO: Provide details about parsing in the error.
-        Err(ParseError::new(expression).into_owned().into())
-    })
+    .map(|(_, treeish)| treeish)
+    .unwrap_or_else(|_: ErrorMode| Err(ParseError::new(expression, separator).into_owned().into()))
+}
+
+// Finds the first occurrence of `separator` in `input` that is not escaped by an immediately
+// preceding `\`, returning the unescaped text before it, the remainder of `input` following it,
+// and the byte offset (into `input`) at which that remainder begins. Fails (so that callers fall
+// back to treating the whole expression as a bare path or glob) if no unescaped `separator` is
+// found.
+fn split_at_separator<'i>(input: &'i str, separator: &str) -> Option<(Cow<'i, str>, &'i str, usize)> {
+    let mut search = 0;
+    loop {
+        let found = input[search..].find(separator)? + search;
+        if input[..found].ends_with('\\') {
+            search = found + separator.len();
+            continue;
+        }
+        let offset = found + separator.len();
+        return Some((unescape(&input[..found], separator), &input[offset..], offset));
+    }
+}
+
+fn separator_split<'i, 's>(
+    separator: &'s str,
+) -> impl FnMut(Input<'i>) -> nom::IResult<Input<'i>, (Cow<'i, str>, Input<'i>, usize), ErrorStack<'i>> + 's
+{
+    move |input: Input<'i>| match split_at_separator(input, separator) {
+        Some((tree, rest, offset)) => Ok(("", (tree, rest, offset))),
+        None => Err(nom::Err::Error(NomError { errors: Vec::new() })),
+    }
+}
+
+// Replaces any literal `\<separator>` in `input` with `separator`, leaving other occurrences of
+// `\` untouched. `\\<separator>` (an escaped `\` followed by a real separator) is not supported;
+// a `\` is always read as escaping the separator that immediately follows it.
+fn unescape<'i>(input: &'i str, separator: &str) -> Cow<'i, str> {
+    let escaped = format!("\\{}", separator);
+    if input.contains(escaped.as_str()) {
+        Cow::Owned(input.replace(escaped.as_str(), separator))
+    }
+    else {
+        Cow::Borrowed(input)
+    }
+}
+
+// Splits `input` into `separator`-delimited segments exactly like `str::split` would (always yielding at
+// least one segment, even for an empty `input`), but, like `split_at_separator`, treats a
+// backslash-escaped separator as literal text within a segment rather than a split point, and
+// unescapes each segment accordingly. Pairs each segment with the *raw* (still possibly-escaped)
+// span it came from, given that `input` itself begins at `offset` within the larger expression.
+fn split_segments<'i>(
+    mut input: &'i str,
+    separator: &str,
+    mut offset: usize,
+) -> Vec<(Cow<'i, str>, (usize, usize))> {
+    let mut segments = Vec::new();
+    loop {
+        match split_at_separator(input, separator) {
+            Some((segment, rest, consumed)) => {
+                segments.push((segment, (offset, offset + consumed - separator.len())));
+                offset += consumed;
+                input = rest;
+            },
+            None => {
+                segments.push((unescape(input, separator), (offset, offset + input.len())));
+                return segments;
+            },
+        }
+    }
+}
+
+// Strips a leading `!` from `segment`, preserving whichever of `Cow`'s variants it was passed as
+// (so that a `Cow::Borrowed` segment stays zero-copy).
+fn strip_bang<'i>(segment: Cow<'i, str>) -> Option<Cow<'i, str>> {
+    match segment {
+        Cow::Borrowed(segment) => segment.strip_prefix('!').map(Cow::Borrowed),
+        Cow::Owned(mut segment) => {
+            if segment.starts_with('!') {
+                segment.remove(0);
+                Some(Cow::Owned(segment))
+            }
+            else {
+                None
+            }
+        },
+    }
+}
+
+// Compiles `text` into a `Glob`, preserving whichever of `Cow`'s variants it was passed as (so
+// that a `Cow::Borrowed` fragment, the common case, compiles a zero-copy `Glob` borrowing directly
+// from the original expression).
+fn build_glob<'i>(text: Cow<'i, str>) -> Result<Glob<'i>, wax::BuildError> {
+    match text {
+        Cow::Borrowed(text) => Glob::new(text),
+        Cow::Owned(ref text) => Glob::new(text).map(Glob::into_owned),
+    }
+}
+
+// Parses the portion of a treeish expression following the first separator: an inclusion glob,
+// optionally followed by any number of further separator-delimited exclusion globs, each of which
+// must begin with `!`, e.g. `src::**/*.rs::!**/target/**`. `offset` is the byte position of `rest`
+// within the full expression, used to keep glob compilation errors pointing at the right span.
+fn parse_tree<'i>(
+    expression: &str,
+    separator: &str,
+    tree: Cow<'i, str>,
+    rest: &'i str,
+    offset: usize,
+) -> Result<Option<Partitioned<'i>>, BuildError> {
+    // The span of the tree path in the full expression, used to label diagnostics; `None` if
+    // there is no tree path (the expression began with the separator).
+    let tree_span = (!tree.is_empty()).then_some((0, offset - separator.len()));
+
+    let mut segments = split_segments(rest, separator, offset).into_iter();
+    // `split_segments` always yields at least one segment, even for an empty string.
+    let (include_text, include_span) = segments.next().unwrap();
+    let include = build_glob(include_text).map_err(|error| {
+        ParseError::for_fragment(expression, tree_span, include_span, ParseErrorCause::Glob(error))
+    })?;
+
+    let mut excludes = Vec::new();
+    // The span of the most recently parsed exclusion segment, used to label `ExclusionWithoutTree`
+    // below; only ever read once at least one exclusion has been seen.
+    let mut exclude_span = include_span;
+    for (segment, span) in segments {
+        exclude_span = span;
+        let pattern = strip_bang(segment).ok_or_else(|| {
+            ParseError::for_fragment(
+                expression,
+                tree_span,
+                exclude_span,
+                ParseErrorCause::Rule(RuleError::MalformedExclusion),
+            )
+        })?;
+        // The bang is always a literal single byte at the start of the raw segment too, whether or
+        // not the segment contains an escaped separator, so the fragment span is simply shifted by
+        // one to skip over it.
+        let pattern_span = (exclude_span.0 + 1, exclude_span.1);
+        excludes.push(build_glob(pattern).map_err(|error| {
+            ParseError::for_fragment(expression, tree_span, pattern_span, ParseErrorCause::Glob(error))
+        })?);
+    }
+
+    let tree = if tree.is_empty() {
+        None
+    }
+    else {
+        Some(match tree {
+            Cow::Borrowed(tree) => Cow::Borrowed(Path::new(tree)),
+            Cow::Owned(tree) => Cow::Owned(PathBuf::from(tree)),
+        })
+    };
+    Ok(Some(match tree {
+        Some(tree) => Partitioned::GlobIn(tree, include, excludes),
+        None if excludes.is_empty() => Partitioned::Glob(include),
+        None => {
+            return Err(ParseError::for_fragment(
+                expression,
+                tree_span,
+                exclude_span,
+                ParseErrorCause::Rule(RuleError::ExclusionWithoutTree),
+            )
+            .into());
+        },
+    }))
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::BuildErrorKind;
+
+    fn parse_error(expression: &str) -> ParseError<'static> {
+        match parse(expression).unwrap_err().kind {
+            BuildErrorKind::Parse(error) => error,
+            kind => panic!("expected `BuildErrorKind::Parse`, got {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn malformed_exclusion_segment_yields_parse_error_with_spans() {
+        let error = parse_error("src::**/*.rs::not-an-exclusion");
+        assert_eq!(error.tree, Some((0, 3)));
+        assert_eq!(error.glob, Some((14, 30)));
+    }
+
+    #[test]
+    fn exclusion_without_tree_yields_parse_error() {
+        let error = parse_error("::**/*.rs::!**/target/**");
+        assert_eq!(error.tree, None);
+        assert_eq!(error.glob, Some((11, 24)));
+    }
+
+    #[cfg(feature = "miette")]
+    #[test]
+    fn parse_error_labels_tree_and_glob_fragments() {
+        let error = parse_error("src::**/*.rs::not-an-exclusion");
+        let labels: Vec<_> = error.labels().unwrap().collect();
+        assert!(labels
+            .iter()
+            .any(|label| label.label() == Some("in this tree path")));
+        assert!(labels
+            .iter()
+            .any(|label| label.label() == Some("in this glob pattern")));
+    }
+
+    // An exclusion glob containing a literal, backslash-escaped separator must survive a
+    // `Display`/re-parse round trip: the segment split that recovers it from the expression has
+    // to be just as escape-aware as the split that recovers the tree path.
+    #[test]
+    fn display_round_trips_exclusion_glob_containing_escaped_separator() {
+        let expression = "src::**/*.rs::!a\\::b/**";
+        let treeish = crate::Treeish::new(expression).unwrap();
+        let displayed = treeish.to_string();
+        assert_eq!(displayed, expression);
+        let reparsed = crate::Treeish::new(&displayed).unwrap();
+        assert_eq!(reparsed.to_string(), displayed);
+    }
+}